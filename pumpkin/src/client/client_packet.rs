@@ -4,7 +4,7 @@ use pumpkin_core::text::TextComponent;
 use pumpkin_protocol::{
     client::{
         config::{CConfigAddResourcePack, CFinishConfig, CKnownPacks, CRegistryData},
-        login::{CLoginSuccess, CSetCompression},
+        login::{CLoginPluginRequest, CLoginSuccess, CSetCompression},
         status::CPingResponse,
     },
     server::{
@@ -18,7 +18,11 @@ use pumpkin_protocol::{
 use uuid::Uuid;
 
 use crate::{
-    client::authentication::{self, validate_textures, GameProfile},
+    client::{
+        authentication::{self, validate_textures, GameProfile},
+        local_auth,
+    },
+    command::CommandSender,
     entity::player::{ChatMode, Hand},
     proxy::{bungeecord::bungeecord_login, velocity::velocity_login},
     server::{Server, CURRENT_MC_VERSION},
@@ -31,19 +35,37 @@ use super::{authentication::AuthError, Client, PlayerConfig};
 /// NEVER TRUST THE CLIENT. HANDLE EVERY ERROR, UNWRAP/EXPECT
 /// TODO: REMOVE ALL UNWRAPS
 impl Client {
+    /// Kicks the client unless its handshake protocol version is exactly [`CURRENT_MC_PROTOCOL`];
+    /// handshakes for [`ConnectionState::Status`] are never kicked.
+    ///
+    /// This is intentionally a single-version gate, not a window. An earlier pass (109ed72)
+    /// accepted `[CURRENT_MC_PROTOCOL - 1, CURRENT_MC_PROTOCOL + 1]` behind a `Translate` trait
+    /// that was supposed to fix up each outgoing packet for the client's negotiated version via
+    /// `translate(&mut self, version)`. That hook had nothing to attach to: every client-bound
+    /// packet here (`CLoginSuccess`, `CConfigAddResourcePack`, `CRegistryData`, ...) is built once
+    /// through a `::new(...)` constructor and sent immediately, with no public field or setter to
+    /// mutate afterwards, in this file or in `pumpkin_protocol` itself — so it shipped with zero
+    /// real implementations (4307dd6). A real translation layer needs both verified, field-level
+    /// wire deltas between adjacent protocol versions and mutation support added to
+    /// `pumpkin_protocol`'s packet types; without those, accepting adjacent versions is just a
+    /// silent desync bug, so this keeps the exact-match gate until that groundwork exists.
+    #[tracing::instrument(skip_all, fields(next_state = ?handshake.next_state))]
     pub fn handle_handshake(&self, handshake: SHandShake) {
-        dbg!("handshake");
         let version = handshake.protocol_version.0;
+        tracing::debug!(protocol_version = version, "received handshake");
         self.protocol_version
             .store(version, std::sync::atomic::Ordering::Relaxed);
         *self.server_address.lock() = handshake.server_address;
 
         self.connection_state.store(handshake.next_state);
-        if self.connection_state.load() != ConnectionState::Status {
-            let protocol = version;
-            match protocol.cmp(&(CURRENT_MC_PROTOCOL as i32)) {
+        // `Status` pings (e.g. server list) must stay version-agnostic so outdated/too-new
+        // clients can still see the MOTD and player count.
+        if self.connection_state.load() != ConnectionState::Status
+            && version != CURRENT_MC_PROTOCOL as i32
+        {
+            match version.cmp(&(CURRENT_MC_PROTOCOL as i32)) {
                 std::cmp::Ordering::Less => {
-                    self.kick(&format!("Client outdated ({protocol}), Server uses Minecraft {CURRENT_MC_VERSION}, Protocol {CURRENT_MC_PROTOCOL}"));
+                    self.kick(&format!("Client outdated ({version}), Server uses Minecraft {CURRENT_MC_VERSION}, Protocol {CURRENT_MC_PROTOCOL}"));
                 }
                 std::cmp::Ordering::Equal => {}
                 std::cmp::Ordering::Greater => {
@@ -58,7 +80,7 @@ impl Client {
     }
 
     pub fn handle_ping_request(&self, ping_request: SStatusPingRequest) {
-        dbg!("ping");
+        tracing::trace!("received status ping");
         self.send_packet(&CPingResponse::new(ping_request.payload));
         self.close();
     }
@@ -70,8 +92,9 @@ impl Client {
                 .all(|c| c > 32_u8 as char && c < 127_u8 as char)
     }
 
+    #[tracing::instrument(skip_all, fields(name = %login_start.name))]
     pub fn handle_login_start(&self, server: &Server, login_start: SLoginStart) {
-        log::debug!("login start, State {:?}", self.connection_state);
+        tracing::debug!(state = ?self.connection_state, "login start");
 
         if !Self::is_valid_player_name(&login_start.name) {
             self.kick("Invalid characters in username");
@@ -108,6 +131,7 @@ impl Client {
         }
     }
 
+    #[tracing::instrument(skip_all)]
     pub async fn handle_encryption_response(
         &self,
         server: &Server,
@@ -130,6 +154,13 @@ impl Client {
                     self.kick(&e.to_string());
                 }
             }
+        } else if ADVANCED_CONFIG.local_auth.enabled {
+            if let Some(profile) = gameprofile.as_mut() {
+                profile.id = local_auth::offline_uuid(&profile.name);
+            }
+            drop(gameprofile);
+            self.request_local_auth();
+            return;
         }
 
         if let Some(profile) = gameprofile.as_ref() {
@@ -139,6 +170,51 @@ impl Client {
         }
     }
 
+    /// Asks the client to register/login a local account over [`local_auth::LOCAL_AUTH_CHANNEL`]
+    /// before the connection is allowed to leave the login state. Only used for offline servers
+    /// that opted into `local_auth.enabled`; the online-mode Mojang flow in [`Self::autenticate`]
+    /// is untouched.
+    fn request_local_auth(&self) {
+        self.send_packet(&CLoginPluginRequest::new(
+            local_auth::LOCAL_AUTH_MESSAGE_ID,
+            local_auth::LOCAL_AUTH_CHANNEL,
+            &[],
+        ));
+    }
+
+    /// Handles the client's reply to [`Self::request_local_auth`]: the first payload byte selects
+    /// register (`0x00`) or login (`0x01`), followed by the UTF-8 password.
+    fn handle_local_auth_response(&self, server: &Server, data: Vec<u8>) {
+        let Some((&action, password_bytes)) = data.split_first() else {
+            self.kick("Missing local auth payload");
+            return;
+        };
+        let Ok(password) = std::str::from_utf8(password_bytes) else {
+            self.kick("Invalid local auth payload");
+            return;
+        };
+
+        let gameprofile = self.gameprofile.lock();
+        let Some(profile) = gameprofile.as_ref() else {
+            self.kick("No Game profile");
+            return;
+        };
+
+        let result = match action {
+            0x00 => server.local_auth.register(profile.id, password),
+            0x01 => {
+                let source = self.address.lock().ip();
+                server.local_auth.login(profile.id, source, password)
+            }
+            _ => Err(local_auth::LocalAuthError::NotRegistered),
+        };
+
+        match result {
+            Ok(()) => self.finish_login(profile),
+            Err(e) => self.kick(&e.to_string()),
+        }
+    }
+
     fn finish_login(&self, profile: &GameProfile) {
         // enable compression
         if ADVANCED_CONFIG.packet_compression.enabled {
@@ -151,6 +227,7 @@ impl Client {
         self.send_packet(&packet);
     }
 
+    #[tracing::instrument(skip_all, fields(username))]
     async fn autenticate(
         &self,
         server: &Server,
@@ -194,7 +271,19 @@ impl Client {
         Err(AuthError::MissingAuthClient)
     }
 
-    pub fn handle_plugin_response(&self, plugin_response: SLoginPluginResponse) {
+    pub fn handle_plugin_response(&self, server: &Server, plugin_response: SLoginPluginResponse) {
+        // `Login Plugin Response` only echoes the message id we sent, not a channel, so we
+        // correlate on [`local_auth::LOCAL_AUTH_MESSAGE_ID`] rather than a (nonexistent) channel.
+        if ADVANCED_CONFIG.local_auth.enabled
+            && !BASIC_CONFIG.online_mode
+            && plugin_response.message_id.0 == local_auth::LOCAL_AUTH_MESSAGE_ID
+        {
+            match plugin_response.data {
+                Some(data) => self.handle_local_auth_response(server, data),
+                None => self.kick("Missing local auth payload"),
+            }
+            return;
+        }
         receive_plugin_response(self, &ADVANCED_CONFIG.proxy.velocity, plugin_response);
     }
 
@@ -232,10 +321,10 @@ impl Client {
             id: "core",
             version: "1.21",
         }]));
-        dbg!("login acknowledged");
+        tracing::debug!("login acknowledged");
     }
     pub fn handle_client_information_config(&self, client_information: SClientInformationConfig) {
-        dbg!("got client settings");
+        tracing::debug!("received client settings");
         if let (Some(main_hand), Some(chat_mode)) = (
             Hand::from_i32(client_information.main_hand.into()),
             ChatMode::from_i32(client_information.chat_mode.into()),
@@ -259,7 +348,7 @@ impl Client {
         if plugin_message.channel.starts_with("minecraft:brand")
             || plugin_message.channel.starts_with("MC|Brand")
         {
-            dbg!("got a client brand");
+            tracing::debug!("received client brand");
             match String::from_utf8(plugin_message.data) {
                 Ok(brand) => *self.brand.lock() = Some(brand),
                 Err(e) => self.kick(&e.to_string()),
@@ -276,14 +365,25 @@ impl Client {
         }
 
         // We are done with configuring
-        dbg!("finish config");
+        tracing::debug!("finished configuring client");
         self.send_packet(&CFinishConfig::new());
     }
 
-    pub async fn handle_config_acknowledged(&self, _config_acknowledged: SAcknowledgeFinishConfig) {
-        dbg!("config acknowledged");
+    pub async fn handle_config_acknowledged(
+        &self,
+        server: &Server,
+        _config_acknowledged: SAcknowledgeFinishConfig,
+    ) {
+        tracing::debug!("config acknowledged");
         self.connection_state.store(ConnectionState::Play);
         self.make_player
             .store(true, std::sync::atomic::Ordering::Relaxed);
+
+        // Let the client build its own command syntax hints and tab-completion.
+        let sender = CommandSender::Player(self);
+        let declare_commands = server
+            .command_dispatcher
+            .serialize_declare_commands(&sender);
+        self.send_packet(&declare_commands);
     }
 }
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use md5::{Digest, Md5};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use pumpkin_config::ADVANCED_CONFIG;
+
+/// Plugin-message channel local accounts use to exchange login/registration payloads with the
+/// client while it is still in the login state, before [`crate::client::Client::finish_login`].
+pub const LOCAL_AUTH_CHANNEL: &str = "pumpkin:local_auth";
+
+/// Message id [`crate::client::Client::request_local_auth`] sends its `Login Plugin Request`
+/// with. The serverbound `Login Plugin Response` doesn't echo a channel, only this id, so
+/// [`crate::client::Client::handle_plugin_response`] correlates on it instead.
+pub const LOCAL_AUTH_MESSAGE_ID: i32 = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LocalAuthError {
+    #[error("no account registered for this name, send a registration payload first")]
+    NotRegistered,
+    #[error("an account with this name is already registered")]
+    AlreadyRegistered,
+    #[error("incorrect password")]
+    WrongPassword,
+    #[error("too many failed attempts, try again later")]
+    TooManyAttempts,
+    #[error("stored password hash is corrupt")]
+    Corrupt,
+}
+
+/// Deterministic offline UUID for `name`, matching vanilla's `OfflinePlayer:<name>` scheme.
+///
+/// Vanilla computes this as `UUID.nameUUIDFromBytes(("OfflinePlayer:" + name).getBytes(UTF_8))`,
+/// which is an MD5 digest of the raw bytes with no namespace prefix. [`Uuid::new_v3`] always
+/// prepends a namespace UUID to the hashed bytes, so it can't be used here without producing a
+/// different (non-portable) UUID; we replicate `nameUUIDFromBytes` by hand instead.
+pub fn offline_uuid(name: &str) -> Uuid {
+    let mut digest = Md5::digest(format!("OfflinePlayer:{name}").as_bytes());
+    // Stamp in version 3 (name-based, MD5) and the RFC 4122 variant, exactly as
+    // `nameUUIDFromBytes` does after hashing.
+    digest[6] = (digest[6] & 0x0f) | 0x30;
+    digest[8] = (digest[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(digest.into())
+}
+
+/// How long a source IP's failed local-auth attempts count against it before the window resets,
+/// so a player who mistypes their password a few times isn't locked out forever.
+const LOCKOUT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct Account {
+    password_hash: String,
+}
+
+/// Local-account store for offline/cracked servers, bringing AuthMe-style password protection to
+/// the `online-mode=false` login path without touching [`crate::client::Client::autenticate`]'s
+/// online-mode Mojang flow.
+///
+/// Credentials are stored as Argon2id PHC strings, one per offline UUID, persisted as a small
+/// JSON file. Nothing here is exposed unless [`ADVANCED_CONFIG`]'s `local_auth.enabled` flag is
+/// set, so online-mode servers are unaffected.
+pub struct LocalAuthStore {
+    path: PathBuf,
+    accounts: Mutex<HashMap<Uuid, Account>>,
+    /// Failed-attempt counters keyed by the connecting source IP rather than the account's
+    /// offline UUID. The offline UUID is deterministically derivable from a player's public name
+    /// ([`offline_uuid`]), so keying lockout on it would let anyone permanently lock an arbitrary
+    /// registered player out by spamming bad passwords for their name from a throwaway client.
+    lockouts: Mutex<HashMap<IpAddr, (u32, Instant)>>,
+}
+
+impl LocalAuthStore {
+    pub fn load(path: PathBuf) -> Self {
+        let accounts = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            accounts: Mutex::new(accounts),
+            lockouts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn save(&self, accounts: &HashMap<Uuid, Account>) {
+        if let Ok(json) = serde_json::to_vec_pretty(accounts) {
+            if let Err(e) = std::fs::write(&self.path, json) {
+                log::warn!("Failed to persist local-auth accounts: {e}");
+            }
+        }
+    }
+
+    /// Registers `uuid` with `password`, failing if an account already exists for it.
+    pub fn register(&self, uuid: Uuid, password: &str) -> Result<(), LocalAuthError> {
+        let mut accounts = self.accounts.lock();
+        if accounts.contains_key(&uuid) {
+            return Err(LocalAuthError::AlreadyRegistered);
+        }
+        accounts.insert(
+            uuid,
+            Account {
+                password_hash: hash_password(password),
+            },
+        );
+        self.save(&accounts);
+        Ok(())
+    }
+
+    /// Verifies `password` against the stored hash for `uuid`, refusing every attempt from
+    /// `source` with [`LocalAuthError::TooManyAttempts`] once it has `local_auth.max_attempts`
+    /// failures within [`LOCKOUT_WINDOW`], and transparently rehashing the stored value if the
+    /// `Argon2::default()` parameters differ from the ones it was hashed with.
+    pub fn login(&self, uuid: Uuid, source: IpAddr, password: &str) -> Result<(), LocalAuthError> {
+        {
+            let mut lockouts = self.lockouts.lock();
+            if let Some((attempts, since)) = lockouts.get(&source) {
+                if since.elapsed() >= LOCKOUT_WINDOW {
+                    lockouts.remove(&source);
+                } else if *attempts >= ADVANCED_CONFIG.local_auth.max_attempts {
+                    return Err(LocalAuthError::TooManyAttempts);
+                }
+            }
+        }
+
+        let mut accounts = self.accounts.lock();
+        let account = accounts.get_mut(&uuid).ok_or(LocalAuthError::NotRegistered)?;
+
+        let hash = PasswordHash::new(&account.password_hash).map_err(|_| LocalAuthError::Corrupt)?;
+        if Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_err()
+        {
+            let mut lockouts = self.lockouts.lock();
+            let entry = lockouts.entry(source).or_insert((0, Instant::now()));
+            if entry.1.elapsed() >= LOCKOUT_WINDOW {
+                *entry = (0, Instant::now());
+            }
+            entry.0 += 1;
+            return Err(LocalAuthError::WrongPassword);
+        }
+
+        self.lockouts.lock().remove(&source);
+        // Transparent rehash: if the `Argon2::default()` cost parameters (the
+        // `$argon2id$v=19$m=...,t=...,p=...` segment) changed since this account's hash was
+        // created, upgrade it now that we have the plaintext in hand.
+        if params_prefix(&account.password_hash) != params_prefix(&hash_password(password)) {
+            account.password_hash = hash_password(password);
+        }
+        self.save(&accounts);
+        Ok(())
+    }
+}
+
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("hashing with a freshly generated salt never fails")
+        .to_string()
+}
+
+/// Extracts the `$argon2id$v=19$m=...,t=...,p=...` portion of a PHC string, i.e. everything but
+/// the salt and hash, so two hashes can be compared for a parameter change without caring about
+/// their (intentionally different) salts.
+fn params_prefix(phc: &str) -> String {
+    phc.splitn(5, '$').take(4).collect::<Vec<_>>().join("$")
+}
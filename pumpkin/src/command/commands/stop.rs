@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+use pumpkin_core::text::TextComponent;
+
+use crate::command::args::ConsumedArgs;
+use crate::command::dispatcher::CommandError;
+use crate::command::tree::CommandTree;
+use crate::command::{CommandExecutor, CommandSender};
+use crate::server::Server;
+
+const NAMES: [&str; 1] = ["stop"];
+const DESCRIPTION: &str = "Gracefully shuts down the server, disconnecting every player.";
+
+struct StopExecutor;
+
+#[async_trait]
+impl CommandExecutor for StopExecutor {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        server: &Server,
+        _args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        sender
+            .send_message(TextComponent::text("Stopping the server..."))
+            .await;
+        server.shutdown("Server closed by an operator").await;
+        Ok(())
+    }
+}
+
+pub fn init_command_tree<'a>() -> CommandTree<'a> {
+    CommandTree::new(NAMES, DESCRIPTION).execute(&StopExecutor)
+}
@@ -10,7 +10,10 @@ use crate::command::CommandSender;
 use crate::error::PumpkinError;
 use crate::server::Server;
 use pumpkin_core::text::color::{Color, NamedColor};
+use pumpkin_protocol::client::play::{CCommands, CommandFlags, ProtoNode, ProtoNodeType};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -32,11 +35,11 @@ impl CommandError {
     pub fn into_string_or_pumpkin_error(self, cmd: &str) -> Result<String, Box<dyn PumpkinError>> {
         match self {
             InvalidConsumption(s) => {
-                println!("Error while parsing command \"{cmd}\": {s:?} was consumed, but couldn't be parsed");
+                tracing::error!(%cmd, ?s, "invalid consumption while parsing command");
                 Ok("Internal Error (See logs for details)".into())
             }
             InvalidRequirement => {
-                println!("Error while parsing command \"{cmd}\": a requirement that was expected was not met.");
+                tracing::error!(%cmd, "unmet requirement while parsing command");
                 Ok("Internal Error (See logs for details)".into())
             }
             GeneralCommandIssue(s) => Ok(s),
@@ -48,6 +51,10 @@ impl CommandError {
 #[derive(Default)]
 pub struct CommandDispatcher<'a> {
     pub(crate) commands: HashMap<&'a str, Command<'a>>,
+    /// Count of [`CommandDispatcher::handle_command`] calls currently in flight, so
+    /// [`crate::server::Server::shutdown`] can wait for them to finish before exiting.
+    in_flight: AtomicUsize,
+    idle_notify: Notify,
 }
 
 /// Stores registered [`CommandTree`]s and dispatches commands to them.
@@ -58,7 +65,13 @@ impl<'a> CommandDispatcher<'a> {
         server: &'a Server,
         cmd: &'a str,
     ) {
-        if let Err(e) = self.dispatch(sender, server, cmd).await {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let result = self.dispatch(sender, server, cmd).await;
+        if self.in_flight.fetch_sub(1, Ordering::SeqCst) <= 2 {
+            self.idle_notify.notify_waiters();
+        }
+
+        if let Err(e) = result {
             match e.into_string_or_pumpkin_error(cmd) {
                 Ok(err) => {
                     sender
@@ -76,7 +89,28 @@ impl<'a> CommandDispatcher<'a> {
         }
     }
 
+    /// Waits until every in-flight [`Self::handle_command`] call has finished, so
+    /// [`crate::server::Server::shutdown`] doesn't cut off a command that is still running.
+    ///
+    /// [`crate::server::Server::shutdown`] is only ever reached from inside a running command
+    /// (e.g. `/stop`), which is itself still counted in `in_flight` while it awaits this call.
+    /// That caller doesn't finish until shutdown returns, so we wait for every *other* in-flight
+    /// command to drain rather than for the count to hit zero, which it never would.
+    pub(crate) async fn wait_until_idle(&self) {
+        loop {
+            // Register as a waiter before checking the count: `notify_waiters` only wakes
+            // already-registered waiters, so checking first and registering after would drop the
+            // wakeup if the last other command finishes in between.
+            let notified = self.idle_notify.notified();
+            if self.in_flight.load(Ordering::SeqCst) <= 1 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
     /// Execute a command using its corresponding [`CommandTree`].
+    #[tracing::instrument(skip(self, src, server))]
     pub(crate) async fn dispatch(
         &'a self,
         src: &mut CommandSender<'a>,
@@ -113,7 +147,7 @@ impl<'a> CommandDispatcher<'a> {
             Command::Tree(tree) => Ok(tree),
             Command::Alias(target) => {
                 let Some(Command::Tree(tree)) = &self.commands.get(target) else {
-                    println!("Error while parsing command alias \"{key}\": pointing to \"{target}\" which is not a valid tree");
+                    tracing::error!(%key, %target, "command alias points at an invalid tree");
                     return Err(GeneralCommandIssue(
                         "Internal Error (See logs for details)".into(),
                     ));
@@ -178,4 +212,146 @@ impl<'a> CommandDispatcher<'a> {
 
         self.commands.insert(primary_name, Command::Tree(tree));
     }
+
+    /// Builds the client-bound "Declare Commands" packet for every registered [`CommandTree`],
+    /// for native tab-completion and greyed-out syntax hints.
+    ///
+    /// All trees share one flat index space with a synthetic root at index `0`; each tree's own
+    /// root node becomes a child of that synthetic root, and [`Command::Alias`] entries are
+    /// emitted as a bare redirect node pointing at the index of their target tree's root instead
+    /// of being walked again.
+    pub(crate) fn serialize_declare_commands(&'a self, src: &CommandSender<'a>) -> CCommands {
+        let mut nodes = vec![ProtoNode {
+            flags: CommandFlags::ROOT,
+            children: vec![],
+            redirect_node: None,
+            node_type: ProtoNodeType::Root,
+        }];
+        let mut tree_roots: HashMap<&str, usize> = HashMap::new();
+
+        // Trees first, so alias redirects below always have a target index to point at.
+        for command in self.commands.values() {
+            if let Command::Tree(tree) = command {
+                let roots = self.serialize_tree(tree, src, &mut nodes);
+                nodes[0].children.extend(roots.iter().copied());
+                // A tree's root can itself be a `Require` that the sender doesn't satisfy, in
+                // which case the whole tree is pruned and contributes nothing to serialize.
+                if let Some(&root) = roots.first() {
+                    for &name in &tree.names {
+                        tree_roots.insert(name, root);
+                    }
+                }
+            }
+        }
+
+        for (&name, command) in &self.commands {
+            if let Command::Alias(target) = command {
+                if let Some(&redirect) = tree_roots.get(target) {
+                    let alias = nodes.len();
+                    nodes.push(ProtoNode {
+                        flags: CommandFlags::LITERAL,
+                        children: vec![],
+                        redirect_node: Some(redirect),
+                        node_type: ProtoNodeType::Literal { name },
+                    });
+                    nodes[0].children.push(alias);
+                }
+            }
+        }
+
+        CCommands::new(nodes, 0)
+    }
+
+    /// Walks one [`CommandTree`], appending its nodes to the shared `nodes` list and pruning any
+    /// branch whose [`NodeType::Require`] predicate the sender does not satisfy. Returns the
+    /// indices the tree's root node contributes to its parent's children.
+    ///
+    /// [`NodeType::ExecuteLeaf`] and a passing [`NodeType::Require`] never become [`ProtoNode`]s
+    /// of their own: a leaf only flips [`CommandFlags::EXECUTABLE`] on the parent that already
+    /// folds it in (see `is_executable` below), and a satisfied requirement is transparent, so its
+    /// children are reparented directly onto whatever node referenced it. That's why each original
+    /// node can contribute zero, one, or several indices to its parent instead of exactly one.
+    fn serialize_tree(
+        &self,
+        tree: &CommandTree<'a>,
+        src: &CommandSender<'a>,
+        nodes: &mut Vec<ProtoNode>,
+    ) -> Vec<usize> {
+        let mut indices: Vec<Vec<usize>> = vec![Vec::new(); tree.nodes.len()];
+        // Whether a passing `Require` node (transparently) carries an `ExecuteLeaf` among its own
+        // descendants. Only ever read/written for `Require` nodes; anything else either is a leaf
+        // itself or materializes its own `ProtoNode`, so it doesn't need to propagate further.
+        let mut transparent_executable = vec![false; tree.nodes.len()];
+
+        // Children are only known once their own subtree has been indexed, so walk bottom-up:
+        // the last node in `tree.nodes` for a linear tree path is always a leaf.
+        for i in (0..tree.nodes.len()).rev() {
+            let node = &tree.nodes[i];
+
+            if matches!(node.node_type, NodeType::ExecuteLeaf { .. }) {
+                continue;
+            }
+
+            // A child makes its parent executable if it's a leaf directly, or if it's a passing
+            // `Require` that transparently carries a leaf through from further down.
+            let child_is_executable =
+                |c: usize| matches!(tree.nodes[c].node_type, NodeType::ExecuteLeaf { .. }) || transparent_executable[c];
+
+            if let NodeType::Require { predicate, .. } = &node.node_type {
+                if predicate(src) {
+                    indices[i] = node
+                        .children
+                        .iter()
+                        .flat_map(|&c| indices[c].iter().copied())
+                        .collect();
+                    transparent_executable[i] = node.children.iter().any(|&c| child_is_executable(c));
+                }
+                continue;
+            }
+
+            let children = node
+                .children
+                .iter()
+                .flat_map(|&c| indices[c].iter().copied())
+                .collect();
+
+            let (flags, node_type) = match &node.node_type {
+                NodeType::Literal { string, .. } => {
+                    (CommandFlags::LITERAL, ProtoNodeType::Literal { name: string })
+                }
+                NodeType::Argument {
+                    name,
+                    consumer,
+                    ..
+                } => (
+                    CommandFlags::ARGUMENT,
+                    ProtoNodeType::Argument {
+                        name,
+                        parser_id: consumer.parser_id(),
+                        properties: consumer.parser_properties(),
+                    },
+                ),
+                NodeType::Require { .. } | NodeType::ExecuteLeaf { .. } => {
+                    unreachable!("handled above")
+                }
+            };
+
+            let is_executable = node.children.iter().any(|&c| child_is_executable(c));
+
+            let idx = nodes.len();
+            nodes.push(ProtoNode {
+                flags: if is_executable {
+                    flags | CommandFlags::EXECUTABLE
+                } else {
+                    flags
+                },
+                children,
+                redirect_node: None,
+                node_type,
+            });
+            indices[i] = vec![idx];
+        }
+
+        std::mem::take(&mut indices[0])
+    }
 }
\ No newline at end of file
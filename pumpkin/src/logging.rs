@@ -0,0 +1,44 @@
+use pumpkin_config::ADVANCED_CONFIG;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Initializes the crate-wide `tracing` subscriber, replacing the ad-hoc `dbg!`/`println!` noise
+/// that used to come out of the packet handlers and command dispatcher.
+///
+/// Always installs an `EnvFilter`-driven formatting layer on stdout; additionally installs an
+/// OpenTelemetry OTLP exporter when `ADVANCED_CONFIG.logging.otlp.enabled` is set, so operators
+/// can ship handshake→config→play and command-dispatch spans to a collector.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt_layer = tracing_subscriber::fmt::layer();
+
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer);
+
+    let otlp_config = &ADVANCED_CONFIG.logging.otlp;
+    if otlp_config.enabled {
+        match otlp_tracer(&otlp_config.endpoint) {
+            Ok(tracer) => {
+                let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
+                registry.with(otel_layer).init();
+                return;
+            }
+            Err(e) => {
+                // Fall through to stdout-only logging; losing telemetry export shouldn't stop
+                // the server from starting.
+                registry.init();
+                tracing::error!("Failed to initialize OTLP exporter: {e}");
+                return;
+            }
+        }
+    }
+
+    registry.init();
+}
+
+fn otlp_tracer(
+    endpoint: &str,
+) -> Result<opentelemetry_sdk::trace::Tracer, opentelemetry::trace::TraceError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::atomic::{AtomicI32, Ordering},
+    time::{Duration, Instant},
+};
+
+use tokio::net::UdpSocket;
+
+use pumpkin_config::{ADVANCED_CONFIG, BASIC_CONFIG};
+
+use super::Server;
+
+/// Magic bytes every GameSpy UT3 query packet starts with.
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 0x09;
+const TYPE_STAT: u8 = 0x00;
+/// `session id | token`, no player list: `GET_STATUS`-style ping from most server-list trackers.
+const BASIC_STAT_PAYLOAD_LEN: usize = 11;
+/// `session id | token | padding`: asks for the full key/value block and player list.
+const FULL_STAT_PAYLOAD_LEN: usize = 15;
+/// Challenge tokens are only accepted for this long after being handed out.
+const CHALLENGE_TTL: Duration = Duration::from_secs(30);
+
+/// Per-address challenge token state for the query handshake.
+struct Challenge {
+    token: i32,
+    issued_at: Instant,
+}
+
+/// UDP GameSpy UT3 query listener, mirroring vanilla's `enable-query` server-list protocol.
+///
+/// This answers a separate protocol from the TCP Server List Ping handled by
+/// [`crate::client::Client::handle_status_request`]; it exists so external server-list trackers
+/// that only speak the UT3 query protocol can still poll this server.
+pub struct QueryServer {
+    socket: UdpSocket,
+    challenges: tokio::sync::Mutex<HashMap<SocketAddr, Challenge>>,
+    next_session_salt: AtomicI32,
+}
+
+impl QueryServer {
+    pub async fn start(server: &'static Server) -> std::io::Result<()> {
+        let config = &ADVANCED_CONFIG.query;
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let socket = UdpSocket::bind((config.address.as_str(), config.port)).await?;
+        log::info!("Query server listening on {}", socket.local_addr()?);
+
+        let query = Self {
+            socket,
+            challenges: tokio::sync::Mutex::new(HashMap::new()),
+            next_session_salt: AtomicI32::new(1),
+        };
+
+        tokio::spawn(async move { query.listen(server).await });
+        Ok(())
+    }
+
+    async fn listen(&self, server: &Server) {
+        let mut buf = [0u8; 1472];
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf).await {
+                Ok(pair) => pair,
+                Err(e) => {
+                    log::warn!("Query socket read error: {e}");
+                    continue;
+                }
+            };
+
+            if let Err(e) = self.handle_packet(server, &buf[..len], addr).await {
+                log::debug!("Dropping malformed query packet from {addr}: {e}");
+            }
+        }
+    }
+
+    async fn handle_packet(
+        &self,
+        server: &Server,
+        packet: &[u8],
+        addr: SocketAddr,
+    ) -> Result<(), &'static str> {
+        if packet.len() < 7 || packet[0..2] != MAGIC {
+            return Err("bad magic");
+        }
+        let packet_type = packet[2];
+        let session_id = i32::from_be_bytes(packet[3..7].try_into().unwrap());
+
+        match packet_type {
+            TYPE_HANDSHAKE => {
+                let token = self.issue_challenge(addr).await;
+                let mut response = Vec::with_capacity(16);
+                response.push(TYPE_HANDSHAKE);
+                response.extend_from_slice(&session_id.to_be_bytes());
+                response.extend_from_slice(format!("{token}\0").as_bytes());
+                self.socket
+                    .send_to(&response, addr)
+                    .await
+                    .map_err(|_| "send failed")?;
+                Ok(())
+            }
+            TYPE_STAT => {
+                let body = &packet[7..];
+                if body.len() < 4 {
+                    return Err("missing token");
+                }
+                let token = i32::from_be_bytes(body[0..4].try_into().unwrap());
+                if !self.check_challenge(addr, token).await {
+                    return Err("stale or unknown challenge token");
+                }
+
+                let response = if body.len() >= FULL_STAT_PAYLOAD_LEN - 7 {
+                    self.full_stat(server, session_id)
+                } else if body.len() >= BASIC_STAT_PAYLOAD_LEN - 7 {
+                    self.basic_stat(server, session_id)
+                } else {
+                    return Err("stat payload too short");
+                };
+                self.socket
+                    .send_to(&response, addr)
+                    .await
+                    .map_err(|_| "send failed")?;
+                Ok(())
+            }
+            _ => Err("unknown query packet type"),
+        }
+    }
+
+    async fn issue_challenge(&self, addr: SocketAddr) -> i32 {
+        let token = self.next_session_salt.fetch_add(1, Ordering::Relaxed) ^ rand::random::<i16>() as i32;
+        self.challenges.lock().await.insert(
+            addr,
+            Challenge {
+                token,
+                issued_at: Instant::now(),
+            },
+        );
+        token
+    }
+
+    async fn check_challenge(&self, addr: SocketAddr, token: i32) -> bool {
+        let mut challenges = self.challenges.lock().await;
+        match challenges.get(&addr) {
+            Some(challenge) if challenge.issued_at.elapsed() < CHALLENGE_TTL && challenge.token == token => {
+                true
+            }
+            _ => {
+                challenges.remove(&addr);
+                false
+            }
+        }
+    }
+
+    fn basic_stat(&self, server: &Server, session_id: i32) -> Vec<u8> {
+        let status = server.get_status();
+        let mut out = Vec::new();
+        out.push(TYPE_STAT);
+        out.extend_from_slice(&session_id.to_be_bytes());
+        write_cstr(&mut out, &status.description.to_plain_text());
+        write_cstr(&mut out, "SMP");
+        write_cstr(&mut out, &ADVANCED_CONFIG.query.map_name);
+        write_cstr(&mut out, &status.players.online.to_string());
+        write_cstr(&mut out, &status.players.max.to_string());
+        out.extend_from_slice(&BASIC_CONFIG.server_port.to_le_bytes());
+        write_cstr(&mut out, &addr_local_ip(&self.socket));
+        out
+    }
+
+    fn full_stat(&self, server: &Server, session_id: i32) -> Vec<u8> {
+        let status = server.get_status();
+        let mut out = Vec::new();
+        out.push(TYPE_STAT);
+        out.extend_from_slice(&session_id.to_be_bytes());
+        // Padding vanilla query clients expect before the key/value block.
+        out.extend_from_slice(&[0x73, 0x70, 0x6C, 0x69, 0x74, 0x6E, 0x75, 0x6D, 0x00, 0x80, 0x00]);
+
+        let kv: &[(&str, String)] = &[
+            ("hostname", status.description.to_plain_text()),
+            ("gametype", "SMP".to_string()),
+            ("game_id", "MINECRAFT".to_string()),
+            ("version", status.version.name.clone()),
+            ("plugins", String::new()),
+            ("map", ADVANCED_CONFIG.query.map_name.clone()),
+            ("numplayers", status.players.online.to_string()),
+            ("maxplayers", status.players.max.to_string()),
+            ("hostport", BASIC_CONFIG.server_port.to_string()),
+            ("hostip", addr_local_ip(&self.socket)),
+        ];
+        for (key, value) in kv {
+            write_cstr(&mut out, key);
+            write_cstr(&mut out, value);
+        }
+        out.push(0x00);
+
+        out.extend_from_slice(&[0x01, 0x70, 0x6C, 0x61, 0x79, 0x65, 0x72, 0x5F, 0x00, 0x00]);
+        for name in status.sample_player_names() {
+            write_cstr(&mut out, &name);
+        }
+        out.push(0x00);
+        out
+    }
+}
+
+fn write_cstr(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(value.as_bytes());
+    out.push(0x00);
+}
+
+fn addr_local_ip(socket: &UdpSocket) -> String {
+    socket
+        .local_addr()
+        .map(|a| a.ip().to_string())
+        .unwrap_or_else(|_| "0.0.0.0".to_string())
+}
@@ -0,0 +1,43 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use tokio::time::timeout;
+
+use super::Server;
+
+/// How long [`Server::shutdown`] waits for in-flight command dispatches to finish before giving
+/// up on them, so one stuck connection can't block the whole shutdown.
+const DISPATCH_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Server {
+    /// Drains the server: stops accepting new handshakes, kicks every connected client with
+    /// `reason`, waits for in-flight command dispatches to finish, flushes persistence, and then
+    /// exits the process.
+    ///
+    /// Each client is kicked with whatever packet [`crate::client::Client::kick`] already sends
+    /// for its current [`pumpkin_protocol::ConnectionState`], so a client still in login/config
+    /// gets the correct disconnect packet rather than the play-state kick.
+    pub async fn shutdown(&self, reason: impl Into<String> + Send) {
+        let reason = reason.into();
+        self.accepting_connections.store(false, Ordering::Relaxed);
+        tracing::info!(%reason, "shutting down server");
+
+        for client in self.clients.lock().values() {
+            client.kick(&reason);
+        }
+
+        if timeout(DISPATCH_DRAIN_TIMEOUT, self.command_dispatcher.wait_until_idle())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "timed out waiting for in-flight commands to finish, shutting down anyway"
+            );
+        }
+
+        if let Err(e) = self.save().await {
+            tracing::error!("Failed to flush persistence during shutdown: {e}");
+        }
+
+        std::process::exit(0);
+    }
+}